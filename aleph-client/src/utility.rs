@@ -1,3 +1,6 @@
+use std::pin::Pin;
+
+use futures::{Stream, StreamExt};
 use log::info;
 use primitives::{BlockNumber, EraIndex, SessionIndex};
 
@@ -7,6 +10,10 @@ use crate::{
     BlockHash,
 };
 
+/// A stream of `(block number, block hash)` pairs for headers as they arrive, or an error for
+/// any header the underlying RPC subscription failed to decode.
+pub type HeaderStream = Pin<Box<dyn Stream<Item = anyhow::Result<(BlockNumber, BlockHash)>> + Send>>;
+
 /// Block info API.
 #[async_trait::async_trait]
 pub trait BlocksApi {
@@ -38,6 +45,23 @@ pub trait BlocksApi {
         &self,
         block: Option<BlockHash>,
     ) -> anyhow::Result<Option<BlockNumber>>;
+
+    /// Subscribes to new best blocks, yielding a `(number, hash)` pair for every block as it
+    /// becomes the best one, or an error for any header the subscription failed to decode. The
+    /// stream terminates when the underlying RPC connection is dropped; it does not resubscribe
+    /// on reconnect, so a caller that needs to keep watching across a reconnect has to call this
+    /// again itself.
+    async fn subscribe_best_blocks(&self) -> anyhow::Result<HeaderStream>;
+
+    /// Subscribes to finalized blocks, yielding a `(number, hash)` pair for every block as it
+    /// gets finalized, or an error for any header the subscription failed to decode. See
+    /// [`BlocksApi::subscribe_best_blocks`] for reconnect semantics.
+    async fn subscribe_finalized_blocks(&self) -> anyhow::Result<HeaderStream>;
+
+    /// Returns which session a given block belongs to. This is the inverse of
+    /// [`BlocksApi::first_block_of_session`].
+    /// * `block` - number of the block
+    async fn session_for_block(&self, block: BlockNumber) -> anyhow::Result<SessionIndex>;
 }
 
 /// Interaction logic between pallet session and pallet staking.
@@ -46,6 +70,15 @@ pub trait SessionEraApi {
     /// Returns which era given session is.
     /// * `session` - session index
     async fn get_active_era_for_session(&self, session: SessionIndex) -> anyhow::Result<EraIndex>;
+
+    /// Returns which era a given block belongs to.
+    /// * `block` - number of the block
+    async fn era_for_block(&self, block: BlockNumber) -> anyhow::Result<EraIndex>;
+
+    /// Returns which era a given block hash belongs to. Handy for callers that already hold a
+    /// `BlockHash`, e.g. from [`BlocksApi::get_finalized_block_hash`].
+    /// * `block` - hash of the block
+    async fn get_active_era_for_block_hash(&self, block: BlockHash) -> anyhow::Result<EraIndex>;
 }
 
 #[async_trait::async_trait]
@@ -99,6 +132,41 @@ impl<C: AsConnection + Sync> BlocksApi for C {
     async fn get_block_number(&self, block: BlockHash) -> anyhow::Result<Option<BlockNumber>> {
         self.get_block_number_opt(Some(block)).await
     }
+
+    async fn subscribe_best_blocks(&self) -> anyhow::Result<HeaderStream> {
+        let subscription = self
+            .as_connection()
+            .as_client()
+            .rpc()
+            .subscribe_new_heads()
+            .await?;
+
+        Ok(Box::pin(subscription.map(|header| {
+            header
+                .map(|header| (header.number, header.hash()))
+                .map_err(|e| e.into())
+        })))
+    }
+
+    async fn subscribe_finalized_blocks(&self) -> anyhow::Result<HeaderStream> {
+        let subscription = self
+            .as_connection()
+            .as_client()
+            .rpc()
+            .subscribe_finalized_heads()
+            .await?;
+
+        Ok(Box::pin(subscription.map(|header| {
+            header
+                .map(|header| (header.number, header.hash()))
+                .map_err(|e| e.into())
+        })))
+    }
+
+    async fn session_for_block(&self, block: BlockNumber) -> anyhow::Result<SessionIndex> {
+        let period = self.get_session_period().await?;
+        Ok(block / period)
+    }
 }
 
 #[async_trait::async_trait]
@@ -107,4 +175,25 @@ impl<C: AsConnection + Sync> SessionEraApi for C {
         let block = self.first_block_of_session(session).await?;
         Ok(self.get_active_era(block).await)
     }
+
+    // Not unit tested: the reorg/pruning guard below only matters in combination with a real
+    // `first_block_of_session` RPC call, and this crate has no mock `AsConnection`/RPC layer to
+    // exercise that against.
+    async fn era_for_block(&self, block: BlockNumber) -> anyhow::Result<EraIndex> {
+        let session = self.session_for_block(block).await?;
+        // A reorg or pruning can make `first_block_of_session` come up empty for a session we
+        // just computed by dividing; don't trust the division in that case and report an error
+        // instead of fabricating an era. A block that predates the first staking era is a
+        // different case, already handled by `get_active_era_for_session`/`get_active_era`.
+        if self.first_block_of_session(session).await?.is_none() {
+            anyhow::bail!(
+                "cannot determine era for block #{block}: first block of session {session} is not available, possibly due to a reorg or pruning"
+            );
+        }
+        self.get_active_era_for_session(session).await
+    }
+
+    async fn get_active_era_for_block_hash(&self, block: BlockHash) -> anyhow::Result<EraIndex> {
+        Ok(self.get_active_era(Some(block)).await)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,46 @@
+use std::sync::Arc;
+
+use sc_network_common::ExHashT;
+use sc_service::SpawnTaskHandle;
+use sp_runtime::traits::{Block, NumberFor};
+
+use crate::network::substrate::SubstrateNetwork;
+
+pub mod network;
+pub mod nodes;
+pub mod session_map;
+
+/// A read-only view of locally available block bodies. Used to answer on-demand
+/// `Protocol::BlockSyncRequest`s without needing a full client.
+pub trait BlockchainBackend<B: Block> {
+    /// Returns the block with the given number, if we have it.
+    fn block(&self, number: NumberFor<B>) -> Option<B>;
+}
+
+/// A client implementation usable by the finality gadget: anything exposing a runtime API
+/// compatible with block `B` over backend `BE`.
+pub trait ClientForAleph<B: Block, BE: sc_client_api::Backend<B>> {
+    /// The runtime API this client exposes.
+    type Api;
+}
+
+/// Configuration needed to run an Aleph Zero node, whether as a validator or not.
+///
+/// `M` is the metrics type and `J` the justification-notification receiver type; both are left
+/// generic here because the code that produces them lives outside `nodes`, which only threads
+/// them through without inspecting them.
+pub struct AlephConfig<B: Block, H: ExHashT, C, SC, BB, M, J> {
+    pub network: SubstrateNetwork<B, H>,
+    pub client: Arc<C>,
+    pub select_chain: SC,
+    pub blockchain_backend: BB,
+    pub metrics: M,
+    pub session_period: aleph_primitives::SessionPeriod,
+    pub millisecs_per_block: u64,
+    pub justification_rx: J,
+    pub spawn_handle: SpawnTaskHandle,
+    /// Whether this node should additionally answer `Protocol::BlockSyncRequest`s for blocks it
+    /// has archived, on top of the gossip-based block sync every node already participates in.
+    /// Only consulted by `nodes::run_nonvalidator_node`; validator nodes ignore it.
+    pub serve_block_requests: bool,
+}
@@ -0,0 +1,2 @@
+pub mod gossip;
+pub mod substrate;
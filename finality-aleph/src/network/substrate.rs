@@ -1,12 +1,22 @@
-use std::{collections::HashMap, fmt, iter, pin::Pin, sync::Arc};
+use std::{
+    collections::HashMap,
+    fmt, iter,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use async_trait::async_trait;
-use futures::stream::{Stream, StreamExt};
+use futures::{
+    channel::{mpsc, oneshot},
+    stream::{self, Stream, StreamExt},
+};
 use log::{error, trace, warn};
 use sc_network::{
-    multiaddr::Protocol as MultiaddressProtocol, Event as SubstrateEvent, Multiaddr,
-    NetworkEventStream as _, NetworkNotification, NetworkPeers, NetworkService,
-    NotificationSenderT, PeerId, ProtocolName,
+    config::{IncomingRequest, OutgoingResponse},
+    multiaddr::Protocol as MultiaddressProtocol, Event as SubstrateEvent, IfDisconnected,
+    Multiaddr, NetworkEventStream as _, NetworkNotification, NetworkPeers, NetworkRequest,
+    NetworkService, NotificationSenderT, PeerId, ProtocolName, ReputationChange,
 };
 use sc_network_common::{
     sync::{SyncEvent, SyncEventStream},
@@ -16,7 +26,7 @@ use sc_network_sync::SyncingService;
 use sp_runtime::traits::Block;
 use tokio::select;
 
-use crate::network::gossip::{Event, EventStream, NetworkSender, Protocol, RawNetwork};
+use crate::network::gossip::{Event, EventStream, Misbehavior, NetworkSender, Protocol, RawNetwork};
 
 /// Name of the network protocol used by Aleph Zero to disseminate validator
 /// authentications.
@@ -29,58 +39,138 @@ const LEGACY_AUTHENTICATION_PROTOCOL_NAME: &str = "/aleph/1";
 /// Name of the network protocol used by Aleph Zero to synchronize the block state.
 const BLOCK_SYNC_PROTOCOL_NAME: &str = "/sync/0";
 
+/// Name of the network protocol used by Aleph Zero to explicitly request ranges of blocks from
+/// a chosen peer, instead of waiting for them to be gossiped.
+const BLOCK_SYNC_REQUEST_PROTOCOL_NAME: &str = "/sync/req/0";
+
+/// Protocols that behave like notification streams, i.e. the ones whose peers should be kept in
+/// the reserved set. Request/response protocols, like `Protocol::BlockSyncRequest`, are
+/// connected to on demand and must not be reserved this way.
+const NOTIFICATION_PROTOCOLS: [Protocol; 2] = [Protocol::Authentication, Protocol::BlockSync];
+
+/// Every `Protocol` variant, used to check that a `ProtocolNaming` registry is complete.
+const ALL_PROTOCOLS: [Protocol; 3] = [
+    Protocol::Authentication,
+    Protocol::BlockSync,
+    Protocol::BlockSyncRequest,
+];
+
+/// How long we wait for a response to an outgoing request before giving up.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Reputation penalty applied for a mild misbehavior, e.g. an occasional malformed message.
+const MILD_PENALTY: i32 = -100;
+
+/// Reputation penalty applied for a severe misbehavior, e.g. an invalid block-sync payload.
+const SEVERE_PENALTY: i32 = -10_000;
+
+/// How much of its accumulated penalty a peer recovers per second, so that a peer who
+/// misbehaved a while ago is not banned forever.
+const REPUTATION_RECOVERY_PER_SECOND: i32 = 1;
+
+/// A single named version of a protocol's wire format, given as the version number together
+/// with the suffix used to build its protocol name.
+pub type VersionedName = (u32, &'static str);
+
+/// A protocol together with the ordered list of versions it supports. The highest version
+/// becomes the canonical name that we advertise and dial with, the rest become fallbacks that we
+/// still recognize while peers are upgrading.
+pub struct ProtocolSpec {
+    pub protocol: Protocol,
+    pub versions: Vec<VersionedName>,
+}
+
 /// Convert protocols to their names and vice versa.
 #[derive(Clone)]
 pub struct ProtocolNaming {
-    authentication_name: ProtocolName,
-    authentication_fallback_names: Vec<ProtocolName>,
-    block_sync_name: ProtocolName,
-    protocols_by_name: HashMap<ProtocolName, Protocol>,
+    canonical_names: HashMap<Protocol, ProtocolName>,
+    fallback_names: HashMap<Protocol, Vec<ProtocolName>>,
+    protocols_by_name: HashMap<ProtocolName, (Protocol, u32)>,
 }
 
 impl ProtocolNaming {
-    /// Create a new protocol naming scheme with the given chain prefix.
+    /// Create a new protocol naming scheme with the given chain prefix, using the protocols
+    /// currently known to Aleph Zero: authentication with its legacy name as a fallback, and
+    /// single-version block sync and block sync request protocols.
     pub fn new(chain_prefix: String) -> Self {
-        let authentication_name: ProtocolName =
-            format!("{chain_prefix}{AUTHENTICATION_PROTOCOL_NAME}").into();
+        Self::with_protocols(
+            chain_prefix,
+            vec![
+                ProtocolSpec {
+                    protocol: Protocol::Authentication,
+                    versions: vec![
+                        (0, LEGACY_AUTHENTICATION_PROTOCOL_NAME),
+                        (1, AUTHENTICATION_PROTOCOL_NAME),
+                    ],
+                },
+                ProtocolSpec {
+                    protocol: Protocol::BlockSync,
+                    versions: vec![(0, BLOCK_SYNC_PROTOCOL_NAME)],
+                },
+                ProtocolSpec {
+                    protocol: Protocol::BlockSyncRequest,
+                    versions: vec![(0, BLOCK_SYNC_REQUEST_PROTOCOL_NAME)],
+                },
+            ],
+        )
+    }
+
+    /// Create a new protocol naming scheme with the given chain prefix and an explicit registry
+    /// of protocols, each with its own ordered list of `(version, suffix)` entries.
+    pub fn with_protocols(chain_prefix: String, protocols: Vec<ProtocolSpec>) -> Self {
+        let mut canonical_names = HashMap::new();
+        let mut fallback_names = HashMap::new();
         let mut protocols_by_name = HashMap::new();
-        protocols_by_name.insert(authentication_name.clone(), Protocol::Authentication);
-        let authentication_fallback_names: Vec<ProtocolName> =
-            vec![LEGACY_AUTHENTICATION_PROTOCOL_NAME.into()];
-        for protocol_name in &authentication_fallback_names {
-            protocols_by_name.insert(protocol_name.clone(), Protocol::Authentication);
+        for ProtocolSpec { protocol, versions } in protocols {
+            let mut versions = versions;
+            versions.sort_by_key(|(version, _)| *version);
+            let mut versions = versions.into_iter().rev();
+            let (canonical_version, canonical_suffix) = versions
+                .next()
+                .expect("every protocol must have at least one version");
+            let canonical_name: ProtocolName =
+                format!("{chain_prefix}{canonical_suffix}").into();
+            protocols_by_name.insert(canonical_name.clone(), (protocol, canonical_version));
+            canonical_names.insert(protocol, canonical_name);
+
+            let fallbacks = versions
+                .map(|(version, suffix)| {
+                    let name: ProtocolName = format!("{chain_prefix}{suffix}").into();
+                    protocols_by_name.insert(name.clone(), (protocol, version));
+                    name
+                })
+                .collect();
+            fallback_names.insert(protocol, fallbacks);
         }
-        let block_sync_name: ProtocolName =
-            format!("{chain_prefix}{BLOCK_SYNC_PROTOCOL_NAME}").into();
-        protocols_by_name.insert(block_sync_name.clone(), Protocol::BlockSync);
+        debug_assert!(
+            ALL_PROTOCOLS
+                .iter()
+                .all(|protocol| canonical_names.contains_key(protocol)),
+            "ProtocolNaming::with_protocols must be given a name for every Protocol variant"
+        );
         ProtocolNaming {
-            authentication_name,
-            authentication_fallback_names,
-            block_sync_name,
+            canonical_names,
+            fallback_names,
             protocols_by_name,
         }
     }
 
     /// Returns the canonical name of the protocol.
     pub fn protocol_name(&self, protocol: &Protocol) -> ProtocolName {
-        use Protocol::*;
-        match protocol {
-            Authentication => self.authentication_name.clone(),
-            BlockSync => self.block_sync_name.clone(),
-        }
+        self.canonical_names
+            .get(protocol)
+            .cloned()
+            .expect("the protocol naming scheme should know about every protocol")
     }
 
     /// Returns the fallback names of the protocol.
     pub fn fallback_protocol_names(&self, protocol: &Protocol) -> Vec<ProtocolName> {
-        use Protocol::*;
-        match protocol {
-            Authentication => self.authentication_fallback_names.clone(),
-            _ => Vec::new(),
-        }
+        self.fallback_names.get(protocol).cloned().unwrap_or_default()
     }
 
-    /// Attempts to convert the protocol name to a protocol.
-    fn to_protocol(&self, protocol_name: &str) -> Option<Protocol> {
+    /// Attempts to convert the protocol name to a protocol, together with the negotiated
+    /// version.
+    fn to_protocol(&self, protocol_name: &str) -> Option<(Protocol, u32)> {
         self.protocols_by_name.get(protocol_name).copied()
     }
 }
@@ -90,6 +180,8 @@ pub enum SenderError {
     CannotCreateSender(PeerId, Protocol),
     LostConnectionToPeer(PeerId),
     LostConnectionToPeerReady(PeerId),
+    RequestFailed(PeerId, Protocol),
+    RequestTimedOut(PeerId, Protocol),
 }
 
 impl fmt::Display for SenderError {
@@ -113,6 +205,18 @@ impl fmt::Display for SenderError {
                     "Lost connection to peer {peer_id:?} after sender was ready"
                 )
             }
+            SenderError::RequestFailed(peer_id, protocol) => {
+                write!(
+                    f,
+                    "Request to peer {peer_id:?} with protocol {protocol:?} failed"
+                )
+            }
+            SenderError::RequestTimedOut(peer_id, protocol) => {
+                write!(
+                    f,
+                    "Request to peer {peer_id:?} with protocol {protocol:?} timed out"
+                )
+            }
         }
     }
 }
@@ -144,8 +248,11 @@ impl NetworkSender for SubstrateNetworkSender {
 pub struct NetworkEventStream<B: Block, H: ExHashT> {
     stream: Pin<Box<dyn Stream<Item = SubstrateEvent> + Send>>,
     sync_stream: Pin<Box<dyn Stream<Item = SyncEvent> + Send>>,
+    request_stream: Pin<Box<dyn Stream<Item = IncomingRequest> + Send>>,
     naming: ProtocolNaming,
     network: Arc<NetworkService<B, H>>,
+    penalties: Arc<Mutex<HashMap<PeerId, Penalty>>>,
+    ban_threshold: i32,
 }
 
 #[async_trait]
@@ -161,12 +268,14 @@ impl<B: Block, H: ExHashT> EventStream<PeerId> for NetworkEventStream<B, H> {
                         NotificationStreamOpened {
                             remote, protocol, ..
                         } => match self.naming.to_protocol(protocol.as_ref()) {
-                            Some(protocol) => return Some(StreamOpened(remote, protocol)),
+                            Some((protocol, version)) => {
+                                return Some(StreamOpened(remote, protocol, version))
+                            }
                             None => continue,
                         },
                         NotificationStreamClosed { remote, protocol } => {
                             match self.naming.to_protocol(protocol.as_ref()) {
-                                Some(protocol) => return Some(StreamClosed(remote, protocol)),
+                                Some((protocol, _)) => return Some(StreamClosed(remote, protocol)),
                                 None => continue,
                             }
                         }
@@ -178,7 +287,7 @@ impl<B: Block, H: ExHashT> EventStream<PeerId> for NetworkEventStream<B, H> {
                                     .filter_map(|(protocol, data)| {
                                         self.naming
                                             .to_protocol(protocol.as_ref())
-                                            .map(|protocol| (protocol, data))
+                                            .map(|(protocol, _)| (protocol, data))
                                     })
                                     .collect(),
                             ));
@@ -189,42 +298,66 @@ impl<B: Block, H: ExHashT> EventStream<PeerId> for NetworkEventStream<B, H> {
                 Some(event) = self.sync_stream.next() => {
                     match event {
                         PeerConnected(remote) => {
+                            if is_banned(&self.penalties, self.ban_threshold, &remote) {
+                                trace!(target: "aleph-network", "Ignoring connection from banned peer {:?}", remote);
+                                continue;
+                            }
                             let multiaddress: Multiaddr =
                                 iter::once(MultiaddressProtocol::P2p(remote.into())).collect();
                             trace!(target: "aleph-network", "Connected event from address {:?}", multiaddress);
-                            if let Err(e) = self.network.add_peers_to_reserved_set(
-                                self.naming.protocol_name(&Protocol::Authentication),
-                                iter::once(multiaddress.clone()).collect(),
-                            ) {
-                                error!(target: "aleph-network", "add_reserved failed for authentications: {}", e);
-                            }
-                            if let Err(e) = self.network.add_peers_to_reserved_set(
-                                self.naming.protocol_name(&Protocol::BlockSync),
-                                iter::once(multiaddress).collect(),
-                            ) {
-                                error!(target: "aleph-network", "add_reserved failed for block sync: {}", e);
+                            // Only notification protocols use a reserved set; request/response
+                            // protocols, like `Protocol::BlockSyncRequest`, connect on demand.
+                            for protocol in NOTIFICATION_PROTOCOLS {
+                                if let Err(e) = self.network.add_peers_to_reserved_set(
+                                    self.naming.protocol_name(&protocol),
+                                    iter::once(multiaddress.clone()).collect(),
+                                ) {
+                                    error!(target: "aleph-network", "add_reserved failed for {:?}: {}", protocol, e);
+                                }
                             }
                             continue;
                         }
                         PeerDisconnected(remote) => {
                             trace!(target: "aleph-network", "Disconnected event for peer {:?}", remote);
                             let addresses: Vec<_> = iter::once(remote).collect();
-                            if let Err(e) = self.network.remove_peers_from_reserved_set(
-                                self.naming.protocol_name(&Protocol::Authentication),
-                                addresses.clone(),
-                            ) {
-                                warn!(target: "aleph-network", "Error while removing peer from Protocol::Authentication reserved set: {}", e)
-                            }
-                            if let Err(e) = self.network.remove_peers_from_reserved_set(
-                                self.naming.protocol_name(&Protocol::BlockSync),
-                                addresses,
-                            ) {
-                                warn!(target: "aleph-network", "Error while removing peer from Protocol::BlockSync reserved set: {}", e)
+                            for protocol in NOTIFICATION_PROTOCOLS {
+                                if let Err(e) = self.network.remove_peers_from_reserved_set(
+                                    self.naming.protocol_name(&protocol),
+                                    addresses.clone(),
+                                ) {
+                                    warn!(target: "aleph-network", "Error while removing peer from {:?} reserved set: {}", protocol, e)
+                                }
                             }
                             continue;
                         }
                     }
                 },
+                Some(request) = self.request_stream.next() => {
+                    let IncomingRequest { peer, payload, pending_response } = request;
+                    // `Protocol::BlockSyncRequest` peers are never added to a reserved set, so a
+                    // ban placed by `report_peer` would otherwise have no effect on them; reject
+                    // their requests here instead.
+                    if is_banned(&self.penalties, self.ban_threshold, &peer) {
+                        trace!(target: "aleph-network", "Rejecting request from banned peer {:?}", peer);
+                        let _ = pending_response.send(OutgoingResponse {
+                            result: Err(()),
+                            reputation_changes: Vec::new(),
+                            sent_feedback: None,
+                        });
+                        continue;
+                    }
+                    let (responder, response) = oneshot::channel();
+                    tokio::spawn(async move {
+                        if let Ok(data) = response.await {
+                            let _ = pending_response.send(OutgoingResponse {
+                                result: Ok(data),
+                                reputation_changes: Vec::new(),
+                                sent_feedback: None,
+                            });
+                        }
+                    });
+                    return Some(Request(peer, Protocol::BlockSyncRequest, payload, responder));
+                },
                 else => return None,
             }
         }
@@ -237,6 +370,52 @@ pub struct SubstrateNetwork<B: Block, H: ExHashT> {
     network: Arc<NetworkService<B, H>>,
     sync_network: Arc<SyncingService<B>>,
     naming: ProtocolNaming,
+    // Substrate only lets us take the receiving end of a request/response protocol once, but
+    // `event_stream` can be asked to create a new `NetworkEventStream` at any time, so we keep it
+    // behind a mutex and hand it off to the first caller. Every later caller gets a request
+    // stream that never yields anything, instead of a panic.
+    request_receiver: Arc<Mutex<Option<mpsc::Receiver<IncomingRequest>>>>,
+    // Accumulated, decaying reputation penalty per peer, used to decide when to ban a peer from
+    // a protocol's reserved set. Entries are pruned once a peer's penalty decays back to 0, so a
+    // long-running node doesn't leak one entry per ever-seen peer.
+    penalties: Arc<Mutex<HashMap<PeerId, Penalty>>>,
+    ban_threshold: i32,
+}
+
+/// A peer's accumulated reputation penalty, decaying back towards zero over time.
+#[derive(Clone, Copy)]
+struct Penalty {
+    value: i32,
+    last_updated: Instant,
+}
+
+impl Penalty {
+    fn decayed(self, now: Instant) -> i32 {
+        let recovered = now
+            .saturating_duration_since(self.last_updated)
+            .as_secs() as i32
+            * REPUTATION_RECOVERY_PER_SECOND;
+        (self.value + recovered).min(0)
+    }
+}
+
+/// Returns whether `peer_id`'s current, decayed penalty has crossed `ban_threshold`. Prunes
+/// `peer_id`'s entry out of `penalties` if it has fully decayed back to 0, since at that point it
+/// is indistinguishable from a peer we have never penalized.
+fn is_banned(
+    penalties: &Mutex<HashMap<PeerId, Penalty>>,
+    ban_threshold: i32,
+    peer_id: &PeerId,
+) -> bool {
+    let mut penalties = penalties.lock().expect("noone should poison the mutex");
+    let Some(decayed) = penalties.get(peer_id).map(|penalty| penalty.decayed(Instant::now())) else {
+        return false;
+    };
+    if decayed == 0 {
+        penalties.remove(peer_id);
+        return false;
+    }
+    decayed <= ban_threshold
 }
 
 impl<B: Block, H: ExHashT> SubstrateNetwork<B, H> {
@@ -245,15 +424,21 @@ impl<B: Block, H: ExHashT> SubstrateNetwork<B, H> {
         network: Arc<NetworkService<B, H>>,
         sync_network: Arc<SyncingService<B>>,
         naming: ProtocolNaming,
+        request_receiver: mpsc::Receiver<IncomingRequest>,
+        ban_threshold: i32,
     ) -> Self {
         SubstrateNetwork {
             network,
             sync_network,
             naming,
+            request_receiver: Arc::new(Mutex::new(Some(request_receiver))),
+            penalties: Arc::new(Mutex::new(HashMap::new())),
+            ban_threshold,
         }
     }
 }
 
+#[async_trait]
 impl<B: Block, H: ExHashT> RawNetwork for SubstrateNetwork<B, H> {
     type SenderError = SenderError;
     type NetworkSender = SubstrateNetworkSender;
@@ -261,6 +446,18 @@ impl<B: Block, H: ExHashT> RawNetwork for SubstrateNetwork<B, H> {
     type EventStream = NetworkEventStream<B, H>;
 
     fn event_stream(&self) -> Self::EventStream {
+        let request_receiver = self
+            .request_receiver
+            .lock()
+            .expect("noone should poison the mutex")
+            .take();
+        let request_stream: Pin<Box<dyn Stream<Item = IncomingRequest> + Send>> =
+            match request_receiver {
+                Some(request_receiver) => Box::pin(request_receiver),
+                // We already handed the real request channel to an earlier caller; every
+                // `NetworkEventStream` past the first just never sees a `Request`.
+                None => Box::pin(stream::pending()),
+            };
         NetworkEventStream {
             stream: Box::pin(self.network.as_ref().event_stream("aleph-network")),
             sync_stream: Box::pin(
@@ -268,8 +465,11 @@ impl<B: Block, H: ExHashT> RawNetwork for SubstrateNetwork<B, H> {
                     .as_ref()
                     .event_stream("aleph-syncing-network"),
             ),
+            request_stream,
             naming: self.naming.clone(),
             network: self.network.clone(),
+            penalties: self.penalties.clone(),
+            ban_threshold: self.ban_threshold,
         }
     }
 
@@ -288,4 +488,159 @@ impl<B: Block, H: ExHashT> RawNetwork for SubstrateNetwork<B, H> {
             peer_id,
         })
     }
+
+    async fn request(
+        &self,
+        peer_id: PeerId,
+        protocol: Protocol,
+        data: Vec<u8>,
+    ) -> Result<Vec<u8>, SenderError> {
+        let (tx, rx) = oneshot::channel();
+        self.network.start_request(
+            peer_id,
+            self.naming.protocol_name(&protocol),
+            data,
+            None,
+            tx,
+            IfDisconnected::ImmediateError,
+        );
+        match tokio::time::timeout(REQUEST_TIMEOUT, rx).await {
+            Ok(Ok(Ok((response, _)))) => Ok(response),
+            Ok(Ok(Err(_))) | Ok(Err(_)) => Err(SenderError::RequestFailed(peer_id, protocol)),
+            Err(_) => Err(SenderError::RequestTimedOut(peer_id, protocol)),
+        }
+    }
+
+    fn report_peer(&self, peer_id: PeerId, protocol: Protocol, misbehavior: Misbehavior) {
+        trace!(target: "aleph-network", "Peer {:?} reported for {:?} on {:?}", peer_id, misbehavior, protocol);
+        let reputation_change = match misbehavior {
+            Misbehavior::Mild => ReputationChange::new(MILD_PENALTY, "Mild misbehavior"),
+            Misbehavior::Severe => ReputationChange::new(SEVERE_PENALTY, "Severe misbehavior"),
+            Misbehavior::Disable => ReputationChange::new(i32::MIN, "Disabled misbehavior"),
+        };
+        self.network.report_peer(peer_id, reputation_change);
+
+        let now = Instant::now();
+        let mut penalties = self.penalties.lock().expect("noone should poison the mutex");
+        let accumulated = penalties
+            .get(&peer_id)
+            .map(|penalty| penalty.decayed(now))
+            .unwrap_or(0)
+            .saturating_add(reputation_change.value)
+            .min(0);
+        // A fully-decayed penalty (0) is indistinguishable from a peer we never penalized, so
+        // don't keep an entry around for it; that would leak memory for the life of the process
+        // on a long-running node that talks to many transient peers.
+        if accumulated == 0 {
+            penalties.remove(&peer_id);
+        } else {
+            penalties.insert(
+                peer_id,
+                Penalty {
+                    value: accumulated,
+                    last_updated: now,
+                },
+            );
+        }
+        drop(penalties);
+
+        // Reputation is tracked per peer, not per protocol, so a ban applies to the peer as a
+        // whole: drop it from every notification protocol's reserved set. `Protocol` variants
+        // that use request/response semantics instead, like `Protocol::BlockSyncRequest`, are
+        // never reserved in the first place; those are enforced by `NetworkEventStream` checking
+        // `self.penalties` directly before handing out a `Request` event for a banned peer.
+        if matches!(misbehavior, Misbehavior::Disable) || accumulated <= self.ban_threshold {
+            for notification_protocol in NOTIFICATION_PROTOCOLS {
+                if let Err(e) = self.network.remove_peers_from_reserved_set(
+                    self.naming.protocol_name(&notification_protocol),
+                    iter::once(peer_id).collect(),
+                ) {
+                    warn!(target: "aleph-network", "Error while banning peer {:?} from {:?}: {}", peer_id, notification_protocol, e)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn naming() -> ProtocolNaming {
+        ProtocolNaming::with_protocols(
+            "/test".to_string(),
+            vec![
+                ProtocolSpec {
+                    protocol: Protocol::Authentication,
+                    versions: vec![(0, "/auth/0"), (1, "/auth/1")],
+                },
+                ProtocolSpec {
+                    protocol: Protocol::BlockSync,
+                    versions: vec![(0, "/sync/0")],
+                },
+                ProtocolSpec {
+                    protocol: Protocol::BlockSyncRequest,
+                    versions: vec![(0, "/sync/req/0")],
+                },
+            ],
+        )
+    }
+
+    #[test]
+    fn canonical_name_is_the_highest_version() {
+        let naming = naming();
+        assert_eq!(
+            naming.protocol_name(&Protocol::Authentication),
+            ProtocolName::from("/test/auth/1".to_string()),
+        );
+    }
+
+    #[test]
+    fn fallback_names_are_every_lower_version() {
+        let naming = naming();
+        assert_eq!(
+            naming.fallback_protocol_names(&Protocol::Authentication),
+            vec![ProtocolName::from("/test/auth/0".to_string())],
+        );
+    }
+
+    #[test]
+    fn single_version_protocol_has_no_fallbacks() {
+        let naming = naming();
+        assert!(naming.fallback_protocol_names(&Protocol::BlockSync).is_empty());
+    }
+
+    #[test]
+    fn to_protocol_round_trips_canonical_and_fallback_names() {
+        let naming = naming();
+        assert_eq!(
+            naming.to_protocol("/test/auth/1"),
+            Some((Protocol::Authentication, 1)),
+        );
+        assert_eq!(
+            naming.to_protocol("/test/auth/0"),
+            Some((Protocol::Authentication, 0)),
+        );
+        assert_eq!(naming.to_protocol("/test/unknown"), None);
+    }
+
+    #[test]
+    fn penalty_recovers_over_time() {
+        let penalty = Penalty {
+            value: -500,
+            last_updated: Instant::now() - Duration::from_secs(100),
+        };
+        assert_eq!(penalty.decayed(Instant::now()), -400);
+    }
+
+    #[test]
+    fn penalty_fully_recovers_eventually() {
+        let penalty = Penalty {
+            value: -50,
+            last_updated: Instant::now() - Duration::from_secs(1000),
+        };
+        assert_eq!(penalty.decayed(Instant::now()), 0);
+    }
 }
\ No newline at end of file
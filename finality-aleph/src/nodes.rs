@@ -0,0 +1,7 @@
+mod nonvalidator_node;
+
+pub use nonvalidator_node::run_nonvalidator_node;
+
+// `JustificationParams` and `setup_justification_handler`, used by `nonvalidator_node` (and by
+// the validator node), live in the justification-handling code that is outside this checkout.
+// They are not reproduced here; only `run_nonvalidator_node` itself is.
@@ -1,23 +1,37 @@
-use log::{debug, error};
+use std::sync::Arc;
+
+use log::{debug, error, warn};
+use parity_scale_codec::{Decode, Encode};
 use sc_client_api::Backend;
 use sc_network_common::ExHashT;
 use sp_consensus::SelectChain;
-use sp_runtime::traits::Block;
+use sp_runtime::traits::{Block, NumberFor, One};
+use tokio::{select, sync::Semaphore};
 
 use crate::{
+    network::gossip::{Event, EventStream, Misbehavior, Protocol, RawNetwork},
     nodes::{setup_justification_handler, JustificationParams},
     session_map::{AuthorityProviderImpl, FinalityNotificatorImpl, SessionMapUpdater},
     AlephConfig, BlockchainBackend,
 };
 
-pub async fn run_nonvalidator_node<B, H, C, BB, BE, SC>(aleph_config: AlephConfig<B, H, C, SC, BB>)
-where
+/// Maximum number of block-sync requests a serving non-validator node answers concurrently.
+/// Requests past this are dropped instead of queued, and the peers that sent them penalized.
+const MAX_CONCURRENT_BLOCK_REQUESTS: usize = 16;
+
+/// Maximum number of blocks answered for a single `Protocol::BlockSyncRequest`, regardless of
+/// how large a range the peer asked for.
+const MAX_BLOCKS_PER_REQUEST: u32 = 128;
+
+pub async fn run_nonvalidator_node<B, H, C, BB, BE, SC, M, J>(
+    aleph_config: AlephConfig<B, H, C, SC, BB, M, J>,
+) where
     B: Block,
     H: ExHashT,
     C: crate::ClientForAleph<B, BE> + Send + Sync + 'static,
     C::Api: aleph_primitives::AlephSessionApi<B>,
     BE: Backend<B> + 'static,
-    BB: BlockchainBackend<B> + Send + 'static,
+    BB: BlockchainBackend<B> + Clone + Send + 'static,
     SC: SelectChain<B> + 'static,
 {
     let AlephConfig {
@@ -29,6 +43,7 @@ where
         millisecs_per_block,
         justification_rx,
         spawn_handle,
+        serve_block_requests,
         ..
     } = aleph_config;
     let map_updater = SessionMapUpdater::<_, _, B>::new(
@@ -40,6 +55,27 @@ where
         debug!(target: "aleph-party", "SessionMapUpdater has started.");
         map_updater.run(session_period).await
     });
+
+    // Archive non-validators can additionally serve on-demand block-sync requests for peers
+    // that are catching up. We grab our own `NetworkEventStream` for this up front, before
+    // `network` is moved into `JustificationParams` below: `SubstrateNetwork::event_stream` only
+    // hands out the real request channel to its first caller, and the justification handler also
+    // wants an event stream of its own, so whoever asks second would otherwise never see a
+    // `Protocol::BlockSyncRequest`.
+    let request_responder_network = network.clone();
+    let request_responder_backend = blockchain_backend.clone();
+    let request_responder_events =
+        serve_block_requests.then(|| RawNetwork::event_stream(&network));
+    let request_responder = async move {
+        match request_responder_events {
+            Some(events) => {
+                serve_block_sync_requests(request_responder_network, events, request_responder_backend)
+                    .await
+            }
+            None => std::future::pending::<()>().await,
+        }
+    };
+
     let (_, handler_task) = setup_justification_handler(JustificationParams {
         justification_rx,
         network,
@@ -52,6 +88,177 @@ where
     });
 
     debug!(target: "aleph-party", "JustificationHandler has started.");
-    handler_task.await;
-    error!(target: "aleph-party", "JustificationHandler finished.");
-}
\ No newline at end of file
+    select! {
+        _ = handler_task => {
+            error!(target: "aleph-party", "JustificationHandler finished.");
+        },
+        _ = request_responder => {
+            error!(target: "aleph-party", "Block-sync request responder finished unexpectedly.");
+        },
+    }
+}
+
+/// A decoded `Protocol::BlockSyncRequest` payload: a contiguous range of blocks, starting at
+/// `start` and containing up to `count` of them.
+struct BlockRange<B: Block> {
+    start: NumberFor<B>,
+    count: u32,
+}
+
+impl<B: Block> BlockRange<B> {
+    /// Decodes a `start` block number followed by a `count`. Returns `None` if the payload is
+    /// malformed, which the caller should treat as a misbehaving peer.
+    fn decode(payload: &[u8]) -> Option<Self> {
+        let mut input = payload;
+        let start = NumberFor::<B>::decode(&mut input).ok()?;
+        let count = u32::decode(&mut input).ok()?;
+        Some(BlockRange { start, count })
+    }
+}
+
+/// Answers incoming `Protocol::BlockSyncRequest` events read from `events` using
+/// `blockchain_backend`, until `events` ends. Bounds the number of in-flight responses, and
+/// penalizes peers that send more requests than we can currently serve or whose requests don't
+/// even decode. `network` is only used to report misbehaving peers, and is generic over
+/// `RawNetwork` so this can be tested against a fake network instead of a real substrate one.
+async fn serve_block_sync_requests<B, N, BB>(network: N, mut events: N::EventStream, blockchain_backend: BB)
+where
+    B: Block,
+    N: RawNetwork,
+    BB: BlockchainBackend<B> + Clone + Send + 'static,
+{
+    let in_flight = Arc::new(Semaphore::new(MAX_CONCURRENT_BLOCK_REQUESTS));
+    while let Some(event) = events.next_event().await {
+        let Event::Request(peer_id, Protocol::BlockSyncRequest, payload, responder) = event else {
+            continue;
+        };
+        let Some(range) = BlockRange::<B>::decode(&payload) else {
+            warn!(target: "aleph-party", "Dropping undecodable block-sync request from {:?}", peer_id);
+            network.report_peer(peer_id, Protocol::BlockSyncRequest, Misbehavior::Severe);
+            continue;
+        };
+        let Ok(permit) = in_flight.clone().try_acquire_owned() else {
+            warn!(target: "aleph-party", "Dropping block-sync request from {:?}, too many in flight", peer_id);
+            network.report_peer(peer_id, Protocol::BlockSyncRequest, Misbehavior::Mild);
+            continue;
+        };
+        let blockchain_backend = blockchain_backend.clone();
+        tokio::spawn(async move {
+            let _permit = permit;
+            if let Some(response) = answer_block_sync_request::<B, BB>(&blockchain_backend, range) {
+                let _ = responder.send(response);
+            }
+        });
+    }
+}
+
+/// Fetches as many blocks of `range` as we have, in order starting from `range.start`, and
+/// encodes them as the response. Stops early at the first missing block, and never answers with
+/// more than `MAX_BLOCKS_PER_REQUEST` blocks. Returns `None` if we don't have even the first
+/// block, in which case no response is sent.
+fn answer_block_sync_request<B, BB>(blockchain_backend: &BB, range: BlockRange<B>) -> Option<Vec<u8>>
+where
+    B: Block,
+    BB: BlockchainBackend<B>,
+{
+    let BlockRange { mut start, count } = range;
+    let mut blocks = Vec::new();
+    for _ in 0..count.min(MAX_BLOCKS_PER_REQUEST) {
+        let Some(block) = blockchain_backend.block(start) else {
+            break;
+        };
+        blocks.push(block);
+        start += One::one();
+    }
+    (!blocks.is_empty()).then(|| blocks.encode())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use sp_runtime::testing::{Block as TestBlock, Header, TestXt};
+
+    use super::*;
+
+    type Block = TestBlock<TestXt<(), ()>>;
+
+    fn test_block(number: u64) -> Block {
+        Block::new(
+            Header::new(
+                number,
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                Default::default(),
+            ),
+            Vec::new(),
+        )
+    }
+
+    #[derive(Clone, Default)]
+    struct FakeBackend(HashMap<u64, Block>);
+
+    impl BlockchainBackend<Block> for FakeBackend {
+        fn block(&self, number: u64) -> Option<Block> {
+            self.0.get(&number).cloned()
+        }
+    }
+
+    fn backend_with_blocks(numbers: impl IntoIterator<Item = u64>) -> FakeBackend {
+        FakeBackend(numbers.into_iter().map(|n| (n, test_block(n))).collect())
+    }
+
+    #[test]
+    fn decodes_a_well_formed_range() {
+        let payload = (5u64, 3u32).encode();
+        let range = BlockRange::<Block>::decode(&payload).expect("should decode");
+        assert_eq!(range.start, 5);
+        assert_eq!(range.count, 3);
+    }
+
+    #[test]
+    fn rejects_a_truncated_payload() {
+        let payload = 5u64.encode();
+        assert!(BlockRange::<Block>::decode(&payload).is_none());
+    }
+
+    #[test]
+    fn answers_with_every_available_block_in_order() {
+        let backend = backend_with_blocks(0..5);
+        let response = answer_block_sync_request(&backend, BlockRange::<Block> { start: 1, count: 3 })
+            .expect("should have a response");
+        let blocks: Vec<Block> = Decode::decode(&mut &response[..]).expect("should decode");
+        assert_eq!(blocks, vec![test_block(1), test_block(2), test_block(3)]);
+    }
+
+    #[test]
+    fn stops_at_the_first_missing_block() {
+        let backend = backend_with_blocks([0, 1, 3]);
+        let response = answer_block_sync_request(&backend, BlockRange::<Block> { start: 0, count: 5 })
+            .expect("should have a response");
+        let blocks: Vec<Block> = Decode::decode(&mut &response[..]).expect("should decode");
+        assert_eq!(blocks, vec![test_block(0), test_block(1)]);
+    }
+
+    #[test]
+    fn answers_with_nothing_if_even_the_first_block_is_missing() {
+        let backend = backend_with_blocks([1, 2]);
+        assert!(answer_block_sync_request(&backend, BlockRange::<Block> { start: 0, count: 5 }).is_none());
+    }
+
+    #[test]
+    fn never_answers_with_more_than_the_per_request_cap() {
+        let backend = backend_with_blocks(0..(MAX_BLOCKS_PER_REQUEST as u64 + 10));
+        let response = answer_block_sync_request(
+            &backend,
+            BlockRange::<Block> {
+                start: 0,
+                count: MAX_BLOCKS_PER_REQUEST + 10,
+            },
+        )
+        .expect("should have a response");
+        let blocks: Vec<Block> = Decode::decode(&mut &response[..]).expect("should decode");
+        assert_eq!(blocks.len(), MAX_BLOCKS_PER_REQUEST as usize);
+    }
+}
@@ -0,0 +1,3 @@
+// `AuthorityProviderImpl`, `FinalityNotificatorImpl`, and `SessionMapUpdater`, used by
+// `nodes::nonvalidator_node`, live in the session-management code that is outside this checkout.
+// They are not reproduced here.